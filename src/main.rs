@@ -1,5 +1,9 @@
 use std::cmp;
+use std::collections::HashSet;
 
+use noise::{Fbm, NoiseFn, Perlin};
+use rand::seq::SliceRandom;
+use rand::Rng;
 use tcod::colors::*;
 use tcod::console::*;
 use tcod::input::Key;
@@ -9,8 +13,13 @@ const SCREEN_WIDTH: i32 = 80;
 const SCREEN_HEIGHT: i32 = 50;
 const LIMIT_FPS: i32 = 20;
 
-const MAP_WIDTH: i32 = 80;
-const MAP_HEIGHT: i32 = 45;
+const MAP_WIDTH: i32 = 160;
+const MAP_HEIGHT: i32 = 100;
+
+const ROOM_MIN_SIZE: i32 = 6;
+const ROOM_MAX_SIZE: i32 = 10;
+const MAX_ROOMS: i32 = 30;
+const MAX_ROOM_MONSTERS: i32 = 3;
 
 const COLOR_DARK_WALL: Color = Color { r: 0, g: 0, b: 100 };
 const COLOR_DARK_GROUND: Color = Color {
@@ -18,38 +27,121 @@ const COLOR_DARK_GROUND: Color = Color {
     g: 50,
     b: 150,
 };
+const COLOR_LIGHT_WALL: Color = Color {
+    r: 130,
+    g: 110,
+    b: 50,
+};
+const COLOR_LIGHT_GROUND: Color = Color {
+    r: 200,
+    g: 180,
+    b: 50,
+};
+
+// How far (in tiles) the player can see before shadowcasting stops expanding.
+const SIGHT_RADIUS: i32 = 8;
 
 struct Tcod {
     root: Root,
     con: Offscreen,
 }
 
+/// A viewport into the (possibly much larger) world map, following the
+/// player. Recomputed every time the player moves.
+struct Camera {
+    left_x: i32,
+    right_x: i32,
+    top_y: i32,
+    bottom_y: i32,
+}
+
+impl Camera {
+    pub fn new(center_x: i32, center_y: i32, width: i32, height: i32) -> Self {
+        let left_x = (center_x - width / 2).clamp(0, MAP_WIDTH - width);
+        let top_y = (center_y - height / 2).clamp(0, MAP_HEIGHT - height);
+
+        Camera {
+            left_x,
+            right_x: left_x + width,
+            top_y,
+            bottom_y: top_y + height,
+        }
+    }
+
+    fn contains(&self, x: i32, y: i32) -> bool {
+        (self.left_x..self.right_x).contains(&x) && (self.top_y..self.bottom_y).contains(&y)
+    }
+
+    fn to_screen(&self, x: i32, y: i32) -> (i32, i32) {
+        (x - self.left_x, y - self.top_y)
+    }
+}
+
+/// Tracks which cells an `Object` can currently see, recomputed whenever
+/// `dirty` is set (i.e. after the object moves).
+#[derive(Debug, Default)]
+struct Viewshed {
+    visible_tiles: Vec<(i32, i32)>,
+    dirty: bool,
+}
+
+impl Viewshed {
+    pub fn new() -> Self {
+        Viewshed {
+            visible_tiles: Vec::new(),
+            dirty: true,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Object {
     x: i32,
     y: i32,
     char: char,
     color: Color,
+    viewshed: Option<Viewshed>,
 }
 
 impl Object {
     pub fn new(x: i32, y: i32, char: char, color: Color) -> Self {
-        Object { x, y, char, color }
+        Object {
+            x,
+            y,
+            char,
+            color,
+            viewshed: None,
+        }
     }
 
-    pub fn move_by(&mut self, game: &Game, dx: i32, dy: i32) {
+    /// Moves the object by `(dx, dy)` if the destination tile isn't blocked.
+    /// Returns whether it actually moved.
+    pub fn move_by(&mut self, game: &Game, dx: i32, dy: i32) -> bool {
         let x = self.x + dx;
         let y = self.y + dy;
 
-        if !game.tile_at(x, y).blocked {
-            self.x = x;
-            self.y = y;
+        if game.tile_at(x, y).blocked {
+            return false;
+        }
+
+        self.x = x;
+        self.y = y;
+
+        if let Some(viewshed) = &mut self.viewshed {
+            viewshed.dirty = true;
         }
+
+        true
     }
 
-    pub fn draw(&self, con: &mut dyn Console) {
+    pub fn draw(&self, con: &mut dyn Console, camera: &Camera) {
+        if !camera.contains(self.x, self.y) {
+            return;
+        }
+
+        let (screen_x, screen_y) = camera.to_screen(self.x, self.y);
         con.set_default_foreground(self.color);
-        con.put_char(self.x, self.y, self.char, BackgroundFlag::None);
+        con.put_char(screen_x, screen_y, self.char, BackgroundFlag::None);
     }
 }
 
@@ -57,6 +149,7 @@ impl Object {
 struct Tile {
     blocked: bool,
     block_sight: bool,
+    explored: bool,
 }
 
 impl Tile {
@@ -64,6 +157,7 @@ impl Tile {
         Tile {
             blocked: false,
             block_sight: false,
+            explored: false,
         }
     }
 
@@ -71,6 +165,7 @@ impl Tile {
         Tile {
             blocked: true,
             block_sight: true,
+            explored: false,
         }
     }
 }
@@ -93,24 +188,181 @@ impl Rect {
             y2: y + h,
         }
     }
+
+    pub fn center(&self) -> (i32, i32) {
+        ((self.x1 + self.x2) / 2, (self.y1 + self.y2) / 2)
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x1 <= other.x2 && self.x2 >= other.x1 && self.y1 <= other.y2 && self.y2 >= other.y1
+    }
 }
 
 type Map = Vec<Vec<Tile>>;
 
+/// Whose turn it currently is; the main loop alternates between the two so
+/// monsters only act once the player has actually taken a turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunState {
+    PlayerTurn,
+    MonsterTurn,
+}
+
+/// Which algorithm `Game::new` should use to lay out the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GenerationMode {
+    Rooms,
+    Caves,
+}
+
 struct Game {
     map: Map,
+    rooms: Vec<Rect>,
+    player_start: (i32, i32),
+    run_state: RunState,
 }
 
 impl Game {
-    pub fn new() -> Self {
+    pub fn new(mode: GenerationMode) -> Self {
+        let (map, rooms, player_start) = match mode {
+            GenerationMode::Rooms => {
+                let (map, rooms) = make_rooms_map();
+                let player_start = rooms[0].center();
+                (map, rooms, player_start)
+            }
+            GenerationMode::Caves => {
+                let (map, player_start) = make_caves_map();
+                (map, Vec::new(), player_start)
+            }
+        };
+
         Game {
-            map: make_map(),
+            map,
+            rooms,
+            player_start,
+            run_state: RunState::PlayerTurn,
         }
     }
 
     pub fn tile_at(&self, x: i32, y: i32) -> Tile {
         self.map[x as usize][y as usize]
     }
+
+    fn tile_at_mut(&mut self, x: i32, y: i32) -> &mut Tile {
+        &mut self.map[x as usize][y as usize]
+    }
+}
+
+/// Recomputes `player`'s viewshed if it's dirty, and marks every tile it can
+/// now see as explored.
+fn recompute_fov(game: &mut Game, player: &mut Object) {
+    let is_dirty = matches!(&player.viewshed, Some(viewshed) if viewshed.dirty);
+    if !is_dirty {
+        return;
+    }
+
+    let visible_tiles = compute_fov(game, (player.x, player.y), SIGHT_RADIUS);
+    for &(x, y) in &visible_tiles {
+        game.tile_at_mut(x, y).explored = true;
+    }
+
+    if let Some(viewshed) = &mut player.viewshed {
+        viewshed.visible_tiles = visible_tiles;
+        viewshed.dirty = false;
+    }
+}
+
+/// Recursive shadowcasting FOV, scanned over the eight octants around
+/// `origin`. The scan is not symmetric: A seeing B does not guarantee that
+/// B sees A.
+fn compute_fov(game: &Game, origin: (i32, i32), radius: i32) -> Vec<(i32, i32)> {
+    let mut visible = vec![origin];
+
+    for octant in 0..8 {
+        cast_light(game, origin, 1, 1.0, 0.0, radius, octant, &mut visible);
+    }
+
+    visible
+}
+
+// Multipliers that rotate/reflect a (dx, dy) offset in octant 0 into each of
+// the eight octants around the origin.
+const OCTANT_TRANSFORM: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+#[allow(clippy::too_many_arguments)]
+fn cast_light(
+    game: &Game,
+    origin: (i32, i32),
+    row: i32,
+    start_slope: f32,
+    end: f32,
+    radius: i32,
+    octant: usize,
+    visible: &mut Vec<(i32, i32)>,
+) {
+    if start_slope < end {
+        return;
+    }
+
+    let (xx, xy, yx, yy) = OCTANT_TRANSFORM[octant];
+    let radius_sq = (radius * radius) as f32;
+    let mut start = start_slope;
+
+    for i in row..=radius {
+        let dy = -i;
+        let mut blocked = false;
+        let mut new_start = start;
+
+        for dx in -i..=0 {
+            let l_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+            let r_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+
+            if r_slope > start {
+                continue;
+            } else if l_slope < end {
+                break;
+            }
+
+            let map_x = origin.0 + dx * xx + dy * xy;
+            let map_y = origin.1 + dx * yx + dy * yy;
+            let in_bounds = (0..MAP_WIDTH).contains(&map_x) && (0..MAP_HEIGHT).contains(&map_y);
+
+            if (dx * dx + dy * dy) as f32 <= radius_sq && in_bounds {
+                visible.push((map_x, map_y));
+            }
+
+            // Cells off the edge of the map block sight just like a wall, so
+            // the scan never dereferences an out-of-range coordinate.
+            let blocks_sight = !in_bounds || game.tile_at(map_x, map_y).block_sight;
+
+            if blocked {
+                if blocks_sight {
+                    new_start = r_slope;
+                    continue;
+                } else {
+                    blocked = false;
+                    start = new_start;
+                }
+            } else if blocks_sight && i < radius {
+                blocked = true;
+                cast_light(game, origin, i + 1, start, l_slope, radius, octant, visible);
+                new_start = r_slope;
+            }
+        }
+
+        if blocked {
+            break;
+        }
+    }
 }
 
 fn create_room(map: &mut Map, room: Rect) {
@@ -135,16 +387,241 @@ fn create_v_tunnel(map: &mut Map, y1: i32, y2: i32, x: i32) {
     }
 }
 
-fn make_map() -> Map {
+fn make_rooms_map() -> (Map, Vec<Rect>) {
+    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let mut rooms: Vec<Rect> = Vec::new();
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..MAX_ROOMS {
+        let w = rng.gen_range(ROOM_MIN_SIZE..=ROOM_MAX_SIZE);
+        let h = rng.gen_range(ROOM_MIN_SIZE..=ROOM_MAX_SIZE);
+        let x = rng.gen_range(0..(MAP_WIDTH - w - 1));
+        let y = rng.gen_range(0..(MAP_HEIGHT - h - 1));
+
+        let new_room = Rect::new(x, y, w, h);
+        if rooms.iter().any(|room| new_room.intersects(room)) {
+            continue;
+        }
+
+        create_room(&mut map, new_room);
+        let (new_x, new_y) = new_room.center();
+
+        if let Some(prev_room) = rooms.last() {
+            let (prev_x, prev_y) = prev_room.center();
+
+            if rng.gen_bool(0.5) {
+                create_h_tunnel(&mut map, prev_x, new_x, prev_y);
+                create_v_tunnel(&mut map, prev_y, new_y, new_x);
+            } else {
+                create_v_tunnel(&mut map, prev_y, new_y, prev_x);
+                create_h_tunnel(&mut map, prev_x, new_x, new_y);
+            }
+        }
+
+        rooms.push(new_room);
+    }
+
+    (map, rooms)
+}
+
+// Coarser values make bigger, smoother caverns; the cutoff controls how much
+// of the field ends up open floor vs. wall.
+const CAVE_NOISE_SCALE: f64 = 0.05;
+const CAVE_NOISE_THRESHOLD: f64 = 0.0;
+
+fn make_caves_map() -> (Map, (i32, i32)) {
     let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
+    let seed = rand::thread_rng().gen();
+    let noise = Fbm::<Perlin>::new(seed);
+
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            // Keep the outer ring solid, same as the rooms generator, so
+            // there's always a wall bordering the map.
+            let on_border = x == 0 || y == 0 || x == MAP_WIDTH - 1 || y == MAP_HEIGHT - 1;
+
+            let value = noise.get([x as f64 * CAVE_NOISE_SCALE, y as f64 * CAVE_NOISE_SCALE]);
+            map[x as usize][y as usize] = if on_border || value > CAVE_NOISE_THRESHOLD {
+                Tile::wall()
+            } else {
+                Tile::empty()
+            };
+        }
+    }
+
+    let main_region = largest_connected_region(&map);
+    wall_off_other_regions(&mut map, &main_region);
+
+    let player_start = (0..MAP_WIDTH)
+        .flat_map(|x| (0..MAP_HEIGHT).map(move |y| (x, y)))
+        .find(|cell| main_region.contains(cell))
+        .expect("cave generation produced no open region");
+
+    (map, player_start)
+}
+
+/// Flood fill from `start` over unblocked, unvisited tiles, recording every
+/// cell it reaches into `visited` as it goes.
+fn flood_fill(
+    map: &Map,
+    start: (i32, i32),
+    visited: &mut HashSet<(i32, i32)>,
+) -> HashSet<(i32, i32)> {
+    let mut region = HashSet::new();
+    let mut stack = vec![start];
+
+    while let Some((x, y)) = stack.pop() {
+        if !visited.insert((x, y)) {
+            continue;
+        }
+        region.insert((x, y));
+
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let (nx, ny) = (x + dx, y + dy);
+            if (0..MAP_WIDTH).contains(&nx)
+                && (0..MAP_HEIGHT).contains(&ny)
+                && !map[nx as usize][ny as usize].blocked
+                && !visited.contains(&(nx, ny))
+            {
+                stack.push((nx, ny));
+            }
+        }
+    }
+
+    region
+}
+
+/// Finds the largest connected region of floor tiles in `map`.
+fn largest_connected_region(map: &Map) -> HashSet<(i32, i32)> {
+    let mut visited = HashSet::new();
+    let mut largest = HashSet::new();
+
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            if map[x as usize][y as usize].blocked || visited.contains(&(x, y)) {
+                continue;
+            }
+
+            let region = flood_fill(map, (x, y), &mut visited);
+            if region.len() > largest.len() {
+                largest = region;
+            }
+        }
+    }
+
+    largest
+}
+
+/// Walls off every floor tile that isn't part of `main_region`, so the
+/// player can never wander into a disconnected pocket.
+fn wall_off_other_regions(map: &mut Map, main_region: &HashSet<(i32, i32)>) {
+    for x in 0..MAP_WIDTH {
+        for y in 0..MAP_HEIGHT {
+            if !map[x as usize][y as usize].blocked && !main_region.contains(&(x, y)) {
+                map[x as usize][y as usize] = Tile::wall();
+            }
+        }
+    }
+}
+
+fn spawn_monster(rng: &mut impl Rng, x: i32, y: i32) -> Object {
+    if rng.gen_bool(0.8) {
+        Object::new(x, y, 'o', DESATURATED_GREEN)
+    } else {
+        Object::new(x, y, 'T', DARKER_GREEN)
+    }
+}
+
+fn place_monsters(room: &Rect, map: &Map, objects: &mut Vec<Object>) {
+    let mut rng = rand::thread_rng();
+    let num_monsters = rng.gen_range(0..=MAX_ROOM_MONSTERS);
+
+    for _ in 0..num_monsters {
+        let x = rng.gen_range((room.x1 + 1)..room.x2);
+        let y = rng.gen_range((room.y1 + 1)..room.y2);
 
-    let room1 = Rect::new(20, 15, 10, 15);
-    let room2 = Rect::new(50, 15, 10, 15);
-    create_room(&mut map, room1);
-    create_room(&mut map, room2);
-    create_h_tunnel(&mut map, 25, 55, 23);
+        if map[x as usize][y as usize].blocked {
+            continue;
+        }
+
+        objects.push(spawn_monster(&mut rng, x, y));
+    }
+}
+
+// Caves have no rooms to seed monster counts from, so density is expressed
+// as one monster per this many floor tiles instead.
+const CAVE_MONSTER_DENSITY: usize = 40;
+
+fn place_cave_monsters(map: &Map, player_start: (i32, i32), objects: &mut Vec<Object>) {
+    let mut rng = rand::thread_rng();
+    let floor_tiles: Vec<(i32, i32)> = (0..MAP_WIDTH)
+        .flat_map(|x| (0..MAP_HEIGHT).map(move |y| (x, y)))
+        .filter(|&(x, y)| !map[x as usize][y as usize].blocked && (x, y) != player_start)
+        .collect();
+
+    let num_monsters = floor_tiles.len() / CAVE_MONSTER_DENSITY;
+    for _ in 0..num_monsters {
+        let &(x, y) = floor_tiles
+            .choose(&mut rng)
+            .expect("cave has no floor tiles");
+
+        objects.push(spawn_monster(&mut rng, x, y));
+    }
+}
+
+/// Runs one AI step for every non-player `Object`, but only while it's
+/// actually `RunState::MonsterTurn`. `objects[0]` must be the player.
+fn monster_turns(game: &Game, objects: &mut [Object]) {
+    if game.run_state != RunState::MonsterTurn {
+        return;
+    }
+
+    let mut occupied: HashSet<(i32, i32)> = objects.iter().map(|o| (o.x, o.y)).collect();
+
+    let (player, monsters) = objects.split_at_mut(1);
+    let player = &player[0];
+
+    for monster in monsters {
+        let from = (monster.x, monster.y);
+        if ai_take_turn(monster, game, player, &occupied) {
+            occupied.remove(&from);
+            occupied.insert((monster.x, monster.y));
+        }
+    }
+}
+
+/// Chases the player by one tile if it's within the player's sight range and
+/// the destination tile isn't already held by another `Object`, otherwise
+/// stays put. Returns whether it actually moved.
+fn ai_take_turn(
+    monster: &mut Object,
+    game: &Game,
+    player: &Object,
+    occupied: &HashSet<(i32, i32)>,
+) -> bool {
+    let in_sight = player
+        .viewshed
+        .as_ref()
+        .is_some_and(|viewshed| viewshed.visible_tiles.contains(&(monster.x, monster.y)));
+
+    if !in_sight {
+        return false;
+    }
 
-    map
+    let dx = player.x - monster.x;
+    let dy = player.y - monster.y;
+    let (step_x, step_y) = if dx.abs() > dy.abs() {
+        (dx.signum(), 0)
+    } else {
+        (0, dy.signum())
+    };
+
+    let destination = (monster.x + step_x, monster.y + step_y);
+    if occupied.contains(&destination) {
+        return false;
+    }
+
+    monster.move_by(game, step_x, step_y)
 }
 
 fn main() {
@@ -155,46 +632,82 @@ fn main() {
         .title("Roguelike")
         .init();
 
-    let con = Offscreen::new(MAP_WIDTH, MAP_HEIGHT);
+    let con = Offscreen::new(SCREEN_WIDTH, SCREEN_HEIGHT);
 
     let mut tcod = Tcod { root, con };
 
-    let game = Game::new();
-
-    let mut objects = [
-        // Object::new(MAP_WIDTH / 2, MAP_HEIGHT / 2, '@', WHITE),
-        // Object::new(MAP_WIDTH / 2 - 5, MAP_HEIGHT / 2, 'X', YELLOW),
-        Object::new(25, 23, '@', WHITE),
-        Object::new(55, 23, 'X', YELLOW),
-    ];
+    let mode = if rand::thread_rng().gen_bool(0.5) {
+        GenerationMode::Rooms
+    } else {
+        GenerationMode::Caves
+    };
+    let mut game = Game::new(mode);
+
+    let (player_x, player_y) = game.player_start;
+    let mut player = Object::new(player_x, player_y, '@', WHITE);
+    player.viewshed = Some(Viewshed::new());
+
+    let mut objects = vec![player];
+    match mode {
+        GenerationMode::Rooms => {
+            for room in game.rooms.iter().skip(1) {
+                place_monsters(room, &game.map, &mut objects);
+            }
+        }
+        GenerationMode::Caves => {
+            place_cave_monsters(&game.map, game.player_start, &mut objects);
+        }
+    }
 
     tcod::system::set_fps(LIMIT_FPS);
 
     while !tcod.root.window_closed() {
+        recompute_fov(&mut game, &mut objects[0]);
+        let camera = Camera::new(objects[0].x, objects[0].y, SCREEN_WIDTH, SCREEN_HEIGHT);
+
         tcod.con.clear();
 
-        render_all(&mut tcod, &game, &objects);
+        render_all(&mut tcod, &game, &objects, &camera);
 
         tcod.root.flush();
 
         let player = &mut objects[0]; // TODO: this seems icky
-        let exit = handle_keys(&mut tcod, &game, player);
+        let player_action = handle_keys(&mut tcod, &game, player);
 
-        if exit {
+        if player_action == PlayerAction::Exit {
             break;
         }
+
+        game.run_state = if player_action == PlayerAction::TookTurn {
+            RunState::MonsterTurn
+        } else {
+            RunState::PlayerTurn
+        };
+        // Refresh the player's viewshed before the AI reads it, so monsters
+        // react to where the player just moved, not last turn's position.
+        recompute_fov(&mut game, &mut objects[0]);
+        monster_turns(&game, &mut objects);
+        game.run_state = RunState::PlayerTurn;
     }
 }
 
-// Return true to exit, false to continue
-fn handle_keys(tcod: &mut Tcod, game: &Game, player: &mut Object) -> bool {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlayerAction {
+    TookTurn,
+    DidntTakeTurn,
+    Exit,
+}
+
+fn handle_keys(tcod: &mut Tcod, game: &Game, player: &mut Object) -> PlayerAction {
+    use PlayerAction::*;
+
     let key = tcod.root.wait_for_keypress(true);
 
     match key {
-        Key { code: Up, .. } => player.move_by(game, 0, -1),
-        Key { code: Down, .. } => player.move_by(game, 0, 1),
-        Key { code: Left, .. } => player.move_by(game, -1, 0),
-        Key { code: Right, .. } => player.move_by(game, 1, 0),
+        Key { code: Up, .. } => took_turn_if(player.move_by(game, 0, -1)),
+        Key { code: Down, .. } => took_turn_if(player.move_by(game, 0, 1)),
+        Key { code: Left, .. } => took_turn_if(player.move_by(game, -1, 0)),
+        Key { code: Right, .. } => took_turn_if(player.move_by(game, 1, 0)),
 
         Key {
             code: Enter,
@@ -203,30 +716,56 @@ fn handle_keys(tcod: &mut Tcod, game: &Game, player: &mut Object) -> bool {
         } => {
             let fullscreen = tcod.root.is_fullscreen();
             tcod.root.set_fullscreen(!fullscreen);
+            DidntTakeTurn
         }
-        Key { code: Escape, .. } => return true,
+        Key { code: Escape, .. } => Exit,
 
-        _ => {}
+        _ => DidntTakeTurn,
     }
+}
 
-    false
+fn took_turn_if(moved: bool) -> PlayerAction {
+    if moved {
+        PlayerAction::TookTurn
+    } else {
+        PlayerAction::DidntTakeTurn
+    }
 }
 
-fn render_all(tcod: &mut Tcod, game: &Game, objects: &[Object]) {
+fn render_all(tcod: &mut Tcod, game: &Game, objects: &[Object], camera: &Camera) {
+    let visible_tiles = &objects[0]
+        .viewshed
+        .as_ref()
+        .expect("player has no viewshed")
+        .visible_tiles;
+
     for object in objects {
-        object.draw(&mut tcod.con);
+        if visible_tiles.contains(&(object.x, object.y)) {
+            object.draw(&mut tcod.con, camera);
+        }
     }
 
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            let wall = game.map[x as usize][y as usize].block_sight;
-            if wall {
-                tcod.con
-                    .set_char_background(x, y, COLOR_DARK_WALL, BackgroundFlag::Set);
+    for y in camera.top_y..camera.bottom_y {
+        for x in camera.left_x..camera.right_x {
+            let tile = game.tile_at(x, y);
+            let visible = visible_tiles.contains(&(x, y));
+
+            let (wall_color, ground_color) = if visible {
+                (COLOR_LIGHT_WALL, COLOR_LIGHT_GROUND)
+            } else if tile.explored {
+                (COLOR_DARK_WALL, COLOR_DARK_GROUND)
             } else {
-                tcod.con
-                    .set_char_background(x, y, COLOR_DARK_GROUND, BackgroundFlag::Set);
-            }
+                continue;
+            };
+
+            let color = if tile.block_sight {
+                wall_color
+            } else {
+                ground_color
+            };
+            let (screen_x, screen_y) = camera.to_screen(x, y);
+            tcod.con
+                .set_char_background(screen_x, screen_y, color, BackgroundFlag::Set);
         }
     }
 
@@ -234,7 +773,7 @@ fn render_all(tcod: &mut Tcod, game: &Game, objects: &[Object]) {
     blit(
         &tcod.con,
         (0, 0),
-        (MAP_WIDTH, MAP_HEIGHT),
+        (SCREEN_WIDTH, SCREEN_HEIGHT),
         &mut tcod.root,
         (0, 0),
         1.0,